@@ -1,11 +1,30 @@
+use futures_util::{SinkExt, StreamExt};
 use http::StatusCode;
-use poem::{listener::TcpListener, web::Data, EndpointExt, Result, Route};
+use poem::{
+    handler,
+    listener::TcpListener,
+    web::{
+        websocket::{Message, WebSocket},
+        Data,
+    },
+    EndpointExt, IntoResponse, Result, Route,
+};
 use poem_openapi::{
     payload::{Json, PlainText},
     Object, OpenApi, OpenApiService,
 };
-use sdplay_lib::{audio::play, error::SdpPlayerError, stream::Stream, SessionDescriptor};
-use std::net::Ipv4Addr;
+use sdplay_lib::{
+    audio::{self, play},
+    discovery,
+    error::SdpPlayerError,
+    metrics::Metrics,
+    player_state::{PlayerEvent, SharedPlayerState},
+    preset::Preset,
+    sdp_cache,
+    stream::Stream,
+    SessionDescriptor,
+};
+use std::{net::Ipv4Addr, sync::Arc};
 use tokio::{spawn, sync::broadcast};
 use url::Url;
 
@@ -16,64 +35,208 @@ pub struct Status {
     playing: bool,
 }
 
+#[derive(Debug, Clone, Object)]
+pub struct DiscoveredStream {
+    origin: String,
+    message_id_hash: u16,
+    raw_sdp: String,
+}
+
+impl From<discovery::DiscoveredStream> for DiscoveredStream {
+    fn from(stream: discovery::DiscoveredStream) -> Self {
+        DiscoveredStream {
+            origin: stream.origin.to_string(),
+            message_id_hash: stream.message_id_hash,
+            raw_sdp: stream.raw_sdp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Object)]
+pub struct SavePreset {
+    origin: String,
+    message_id_hash: u16,
+    name: String,
+}
+
+#[derive(Debug, Clone, Object)]
+pub struct BackendInfo {
+    available: Vec<String>,
+    selected: Option<String>,
+}
+
 #[OpenApi]
 impl Api {
     #[oai(path = "/play/descriptor", method = "post")]
     async fn play_sd(
         &self,
         Data(stop): Data<&broadcast::Sender<()>>,
+        Data(state): Data<&Arc<SharedPlayerState>>,
+        Data(metrics): Data<&Arc<Metrics>>,
         Json(sd): Json<SessionDescriptor>,
     ) -> Result<Json<&'static str>> {
         log::info!("Playing SessionDescriptor from URL: {sd:?}");
-
-        let local_address = Ipv4Addr::UNSPECIFIED;
-        let stream = Stream::new(sd, local_address)
-            .await
-            .map_err(to_error_response)?;
-        spawn(play(stream, stop.clone()));
-
-        Ok(Json("Ok"))
+        self.start_playing(sd, stop, state, metrics).await
     }
 
     #[oai(path = "/play/url", method = "post")]
-    async fn play_url(&self, Json(url): Json<Url>) -> Result<Json<&'static str>> {
+    async fn play_url(
+        &self,
+        Data(stop): Data<&broadcast::Sender<()>>,
+        Data(state): Data<&Arc<SharedPlayerState>>,
+        Data(metrics): Data<&Arc<Metrics>>,
+        Json(url): Json<Url>,
+    ) -> Result<Json<&'static str>> {
         log::info!("Playing SDP from URL: {url}");
-        // TODO
-        Ok(Json("Ok"))
+        let sd = sdp_cache::fetch_sdp(&url)
+            .await
+            .map_err(|e| poem::Error::from_string(e.to_string(), StatusCode::BAD_GATEWAY))?;
+        self.start_playing(sd, stop, state, metrics).await
     }
 
     #[oai(path = "/play/sdp", method = "post")]
-    async fn play_sdp(&self, PlainText(sdp): PlainText<String>) -> Result<Json<&'static str>> {
+    async fn play_sdp(
+        &self,
+        Data(stop): Data<&broadcast::Sender<()>>,
+        Data(state): Data<&Arc<SharedPlayerState>>,
+        Data(metrics): Data<&Arc<Metrics>>,
+        PlainText(sdp): PlainText<String>,
+    ) -> Result<Json<&'static str>> {
         log::info!("Playing SDP: {sdp}");
-        // TODO
+        let sd = SessionDescriptor::parse(&sdp).map_err(to_error_response)?;
+        self.start_playing(sd, stop, state, metrics).await
+    }
+
+    #[oai(path = "/discover", method = "get")]
+    async fn discover(
+        &self,
+        Data(registry): Data<&Arc<discovery::Registry>>,
+    ) -> Result<Json<Vec<DiscoveredStream>>> {
+        log::info!("Listing discovered streams");
+        let streams = registry.snapshot().await.into_iter().map(Into::into).collect();
+        Ok(Json(streams))
+    }
+
+    #[oai(path = "/discover/save", method = "post")]
+    async fn save_discovered(
+        &self,
+        Data(registry): Data<&Arc<discovery::Registry>>,
+        Json(input): Json<SavePreset>,
+    ) -> Result<Json<&'static str>> {
+        let origin = input.origin.parse().map_err(|_| {
+            poem::Error::from_string("invalid origin address", StatusCode::BAD_REQUEST)
+        })?;
+        let stream = registry
+            .get(&(origin, input.message_id_hash))
+            .await
+            .ok_or_else(|| poem::Error::from_string("no such stream", StatusCode::NOT_FOUND))?;
+
+        let preset = Preset {
+            name: input.name,
+            raw_sdp: Some(stream.raw_sdp),
+            ..Default::default()
+        };
+        sdplay_lib::preset::save_preset(preset)
+            .await
+            .map_err(|e| poem::Error::from_string(e.to_string(), StatusCode::INTERNAL_SERVER_ERROR))?;
+
+        Ok(Json("Ok"))
+    }
+
+    #[oai(path = "/backend", method = "get")]
+    async fn backend(&self, Data(state): Data<&Arc<SharedPlayerState>>) -> Result<Json<BackendInfo>> {
+        log::info!("Getting audio backend info");
+        let selected = state.snapshot().await.backend;
+        let name = |b: audio::Backend| {
+            serde_json::to_value(b)
+                .ok()
+                .and_then(|v| v.as_str().map(str::to_owned))
+        };
+        Ok(Json(BackendInfo {
+            available: audio::Backend::available()
+                .into_iter()
+                .filter_map(name)
+                .collect(),
+            selected: selected.and_then(name),
+        }))
+    }
+
+    #[oai(path = "/backend", method = "post")]
+    async fn set_backend(
+        &self,
+        Data(state): Data<&Arc<SharedPlayerState>>,
+        Json(backend): Json<String>,
+    ) -> Result<Json<&'static str>> {
+        log::info!("Requesting audio backend: {backend}");
+        // `audio::Backend` is plain serde (kebab-case), not a
+        // `poem_openapi::Enum` — sdplay_lib doesn't depend on the web
+        // framework, so we go through its serde impl via a string body,
+        // the same way `backend()` above returns one.
+        let backend: audio::Backend = serde_json::from_value(serde_json::Value::String(backend))
+            .map_err(|_| poem::Error::from_string("unknown audio backend", StatusCode::BAD_REQUEST))?;
+        state.update(|s| s.requested_backend = Some(backend)).await;
         Ok(Json("Ok"))
     }
 
     #[oai(path = "/status", method = "get")]
-    async fn status(&self) -> Result<Json<Status>> {
+    async fn status(&self, Data(state): Data<&Arc<SharedPlayerState>>) -> Result<Json<Status>> {
         log::info!("Getting status");
-        // TODO
-        Ok(Json(Status { playing: true }))
+        let playing = state.snapshot().await.playing;
+        Ok(Json(Status { playing }))
     }
 
     #[oai(path = "/stop", method = "post")]
-    async fn stop(&self, Data(stop): Data<&broadcast::Sender<()>>) -> Result<Json<&'static str>> {
+    async fn stop(
+        &self,
+        Data(stop): Data<&broadcast::Sender<()>>,
+        Data(state): Data<&Arc<SharedPlayerState>>,
+    ) -> Result<Json<&'static str>> {
         log::info!("Stopping receiver");
         stop.send(()).map_err(|e| to_error_response(e.into()))?;
+        state.update(|s| s.playing = false).await;
         Ok(Json("Ok"))
     }
 
     #[oai(path = "/volume", method = "get")]
-    async fn get_volume(&self) -> Result<Json<f32>> {
+    async fn get_volume(&self, Data(state): Data<&Arc<SharedPlayerState>>) -> Result<Json<f32>> {
         log::info!("Getting volume");
-        // TODO
-        Ok(Json(0.5))
+        let volume = state.snapshot().await.volume;
+        Ok(Json(volume))
     }
 
     #[oai(path = "/volume/set", method = "post")]
-    async fn set_volume(&self, Json(volume): Json<f32>) -> Result<Json<&'static str>> {
+    async fn set_volume(
+        &self,
+        Data(state): Data<&Arc<SharedPlayerState>>,
+        Json(volume): Json<f32>,
+    ) -> Result<Json<&'static str>> {
         log::info!("Setting volume to: {volume}");
-        // TODO
+        state.update(|s| s.volume = volume).await;
+        Ok(Json("Ok"))
+    }
+}
+
+impl Api {
+    async fn start_playing(
+        &self,
+        sd: SessionDescriptor,
+        stop: &broadcast::Sender<()>,
+        state: &Arc<SharedPlayerState>,
+        metrics: &Arc<Metrics>,
+    ) -> Result<Json<&'static str>> {
+        let local_address = Ipv4Addr::UNSPECIFIED;
+        let stream = Stream::new(sd.clone(), local_address)
+            .await
+            .map_err(to_error_response)?;
+        spawn(play(stream, stop.clone(), state.clone(), metrics.clone()));
+
+        state
+            .update(|s| {
+                s.current_sdp = Some(sd);
+                s.playing = true;
+            })
+            .await;
+
         Ok(Json("Ok"))
     }
 }
@@ -82,6 +245,59 @@ fn to_error_response(e: SdpPlayerError) -> poem::Error {
     poem::Error::new(e, StatusCode::INTERNAL_SERVER_ERROR)
 }
 
+#[handler]
+fn events(ws: WebSocket, Data(state): Data<&Arc<SharedPlayerState>>) -> impl IntoResponse {
+    let state = state.clone();
+    ws.on_upgrade(move |socket| async move {
+        let (mut sink, _stream) = socket.split();
+        let mut events = state.subscribe();
+
+        // Subscribers only see changes from here on, so a client that
+        // connects mid-stream needs the current state up front instead
+        // of waiting for the next mutation to find out anything's playing.
+        match serde_json::to_string(&PlayerEvent::StateChanged(state.snapshot().await)) {
+            Ok(json) => {
+                if sink.send(Message::Text(json)).await.is_err() {
+                    return;
+                }
+            }
+            Err(e) => log::error!("failed to serialize initial player state: {e}"),
+        }
+
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    log::warn!("/events subscriber lagged, skipped {skipped} events");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let json = match serde_json::to_string(&event) {
+                Ok(json) => json,
+                Err(e) => {
+                    log::error!("failed to serialize player event: {e}");
+                    continue;
+                }
+            };
+            if sink.send(Message::Text(json)).await.is_err() {
+                break;
+            }
+        }
+    })
+}
+
+#[handler]
+fn metrics(Data(metrics): Data<&Arc<Metrics>>) -> impl IntoResponse {
+    match metrics.render() {
+        Ok(text) => (StatusCode::OK, text).into_response(),
+        Err(e) => {
+            log::error!("failed to render metrics: {e}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
 pub async fn start() -> anyhow::Result<()> {
     let public_addr = Ipv4Addr::LOCALHOST;
 
@@ -99,9 +315,23 @@ pub async fn start() -> anyhow::Result<()> {
 
     log::info!("Starting openapi service at {}", public_url);
 
-    // TODO pass this around as state
     let (tx_stop, _rx_stop) = broadcast::channel::<()>(1);
 
+    let metrics_data = Arc::new(Metrics::new());
+    let player_state = SharedPlayerState::new();
+
+    let registry = Arc::new(discovery::Registry::new());
+    spawn({
+        let registry = registry.clone();
+        async move {
+            if let Err(e) = discovery::run(registry).await {
+                log::error!("SAP discovery stopped: {e}");
+            }
+        }
+    });
+
+    let metrics_endpoint = Route::new().at("/", metrics);
+
     let openapi_explorer = api_service.swagger_ui();
     let oapi_spec_json = api_service.spec_endpoint();
     let oapi_spec_yaml = api_service.spec_endpoint_yaml();
@@ -111,7 +341,12 @@ pub async fn start() -> anyhow::Result<()> {
         .nest("/doc", openapi_explorer)
         .nest("/openapi/json", oapi_spec_json)
         .nest("/openapi/yaml", oapi_spec_yaml)
-        .data(tx_stop);
+        .nest("/metrics", metrics_endpoint)
+        .at("/events", events)
+        .data(tx_stop)
+        .data(registry)
+        .data(metrics_data)
+        .data(player_state);
 
     poem::Server::new(TcpListener::bind(addr)).run(app).await?;
 
@@ -0,0 +1,47 @@
+//! ALSA output backend (`alsa-backend` feature), the default on Linux.
+
+use super::AudioSink;
+use crate::{error::SdpPlayerError, preset::CustomStreamSettings};
+use ::alsa::{
+    pcm::{Access, Format, HwParams, PCM},
+    Direction, ValueOr,
+};
+
+pub(super) fn open(settings: &CustomStreamSettings) -> Result<Box<dyn AudioSink>, SdpPlayerError> {
+    let pcm = PCM::new("default", Direction::Playback, false)
+        .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))?;
+    {
+        let hwp = HwParams::any(&pcm).map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))?;
+        hwp.set_channels(settings.channels as u32)
+            .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))?;
+        hwp.set_rate(settings.sample_rate, ValueOr::Nearest)
+            .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))?;
+        hwp.set_format(Format::s16())
+            .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))?;
+        hwp.set_access(Access::RWInterleaved)
+            .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))?;
+        pcm.hw_params(&hwp)
+            .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))?;
+    }
+    Ok(Box::new(AlsaSink { pcm }))
+}
+
+struct AlsaSink {
+    pcm: PCM,
+}
+
+// SAFETY: the PCM handle is only ever touched from the single playback
+// task that owns this sink.
+unsafe impl Send for AlsaSink {}
+
+impl AudioSink for AlsaSink {
+    fn write(&mut self, frame: &[u8]) -> Result<(), SdpPlayerError> {
+        let io = self
+            .pcm
+            .io_bytes()
+            .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))?;
+        io.writei(frame)
+            .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))?;
+        Ok(())
+    }
+}
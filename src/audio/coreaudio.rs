@@ -0,0 +1,89 @@
+//! CoreAudio output backend (`coreaudio-backend` feature), for macOS.
+//!
+//! Like JACK, CoreAudio is pull/callback-driven: the `AudioUnit` calls
+//! back into our code for samples instead of accepting writes, so `write`
+//! feeds a lock-free ring buffer that the render callback drains.
+
+use super::AudioSink;
+use crate::{error::SdpPlayerError, preset::CustomStreamSettings};
+use ::coreaudio::audio_unit::{
+    audio_format::LinearPcmFlags,
+    render_callback::{self, data},
+    AudioUnit, Element, IOType, SampleFormat, StreamFormat,
+};
+use ::rtrb::{Producer, RingBuffer};
+
+pub(super) fn open(settings: &CustomStreamSettings) -> Result<Box<dyn AudioSink>, SdpPlayerError> {
+    let mut audio_unit =
+        AudioUnit::new(IOType::DefaultOutput).map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))?;
+
+    let format = StreamFormat {
+        sample_rate: settings.sample_rate as f64,
+        sample_format: SampleFormat::F32,
+        flags: LinearPcmFlags::IS_FLOAT | LinearPcmFlags::IS_PACKED,
+        channels: settings.channels as u32,
+    };
+    audio_unit
+        .set_stream_format(format, Element::Output)
+        .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))?;
+
+    let channels = settings.channels as usize;
+
+    // One second of headroom, same rationale as the JACK backend: this
+    // stage is meant to stay nearly empty.
+    let (producer, mut consumer) = RingBuffer::new(settings.sample_rate as usize * channels);
+
+    audio_unit
+        .set_render_callback(move |args: render_callback::Args<data::NonInterleaved<f32>>| {
+            let render_callback::Args { num_frames, mut data, .. } = args;
+            for frame in 0..num_frames {
+                // As in the JACK backend: drain a full channel-aligned
+                // frame at a time, or emit silence, so an underrun can't
+                // shift later frames across channels.
+                let has_full_frame = consumer.slots() >= channels;
+                for channel in data.channels_mut() {
+                    channel[frame] = if has_full_frame { consumer.pop().unwrap_or(0.0) } else { 0.0 };
+                }
+            }
+            Ok(())
+        })
+        .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))?;
+
+    audio_unit
+        .start()
+        .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))?;
+
+    Ok(Box::new(CoreAudioSink { _audio_unit: audio_unit, producer, channels }))
+}
+
+struct CoreAudioSink {
+    _audio_unit: AudioUnit,
+    producer: Producer<f32>,
+    channels: usize,
+}
+
+// SAFETY: the AudioUnit handle is only ever touched from the single
+// playback task that owns this sink; the render callback only touches
+// the `Consumer` half of the ring, not the `AudioUnit` itself.
+unsafe impl Send for CoreAudioSink {}
+
+impl AudioSink for CoreAudioSink {
+    fn write(&mut self, frame: &[u8]) -> Result<(), SdpPlayerError> {
+        let bytes_per_frame = 2 * self.channels;
+        for chunk in frame.chunks_exact(bytes_per_frame) {
+            // Push a whole frame at once, or none of it, to keep the
+            // consumer's reads aligned to channel boundaries.
+            if self.producer.slots() < self.channels {
+                log::warn!("CoreAudio ring buffer full, dropping a frame to stay channel-aligned");
+                continue;
+            }
+            for pair in chunk.chunks_exact(2) {
+                let sample = i16::from_le_bytes([pair[0], pair[1]]) as f32 / i16::MAX as f32;
+                self.producer
+                    .push(sample)
+                    .expect("just checked there are enough slots for this frame");
+            }
+        }
+        Ok(())
+    }
+}
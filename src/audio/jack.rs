@@ -0,0 +1,91 @@
+//! JACK output backend (`jack-backend` feature), for pro-audio routing.
+
+use super::AudioSink;
+use crate::{error::SdpPlayerError, preset::CustomStreamSettings};
+use ::jack::{AudioOut, Client, ClientOptions, Port};
+use ::rtrb::{Consumer, Producer, RingBuffer};
+
+pub(super) fn open(settings: &CustomStreamSettings) -> Result<Box<dyn AudioSink>, SdpPlayerError> {
+    let (client, _status) = Client::new(env!("CARGO_PKG_NAME"), ClientOptions::NO_START_SERVER)
+        .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))?;
+
+    let channels = settings.channels as usize;
+    let ports: Vec<Port<AudioOut>> = (0..channels)
+        .map(|i| {
+            client
+                .register_port(&format!("out_{i}"), AudioOut::default())
+                .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))
+        })
+        .collect::<Result<_, _>>()?;
+
+    // A lock-free SPSC ring carries interleaved f32 samples from `write`
+    // (called from the playback task) into the realtime `process`
+    // callback, which isn't allowed to block or allocate. One second of
+    // headroom is generous for a stage that's meant to stay nearly empty.
+    let (producer, consumer) = RingBuffer::new(settings.sample_rate as usize * channels);
+
+    let active = client
+        .activate_async((), JackProcessHandler { ports, consumer, channels })
+        .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))?;
+
+    Ok(Box::new(JackSink { _active: active, producer, channels }))
+}
+
+struct JackProcessHandler {
+    ports: Vec<Port<AudioOut>>,
+    consumer: Consumer<f32>,
+    channels: usize,
+}
+
+impl ::jack::ProcessHandler for JackProcessHandler {
+    fn process(&mut self, _client: &::jack::Client, scope: &::jack::ProcessScope) -> ::jack::Control {
+        let frames = scope.n_frames() as usize;
+        let mut buffers: Vec<&mut [f32]> =
+            self.ports.iter_mut().map(|port| port.as_mut_slice(scope)).collect();
+
+        for frame in 0..frames {
+            // Only drain a full channel-aligned frame at a time: popping
+            // some but not all of a frame's samples would permanently
+            // shift every later frame across channels, since nothing
+            // re-synchronizes reads to frame boundaries afterwards.
+            let has_full_frame = self.consumer.slots() >= self.channels;
+            for buffer in buffers.iter_mut().take(self.channels) {
+                buffer[frame] = if has_full_frame {
+                    self.consumer.pop().unwrap_or(0.0)
+                } else {
+                    0.0
+                };
+            }
+        }
+
+        ::jack::Control::Continue
+    }
+}
+
+struct JackSink {
+    _active: ::jack::AsyncClient<(), JackProcessHandler>,
+    producer: Producer<f32>,
+    channels: usize,
+}
+
+impl AudioSink for JackSink {
+    fn write(&mut self, frame: &[u8]) -> Result<(), SdpPlayerError> {
+        let bytes_per_frame = 2 * self.channels;
+        for chunk in frame.chunks_exact(bytes_per_frame) {
+            // Push a whole frame at once, or none of it: a partial push
+            // would leave the ring buffer's next sample starting mid-frame,
+            // channel-shifting everything the consumer reads after it.
+            if self.producer.slots() < self.channels {
+                log::warn!("JACK ring buffer full, dropping a frame to stay channel-aligned");
+                continue;
+            }
+            for pair in chunk.chunks_exact(2) {
+                let sample = i16::from_le_bytes([pair[0], pair[1]]) as f32 / i16::MAX as f32;
+                self.producer
+                    .push(sample)
+                    .expect("just checked there are enough slots for this frame");
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,236 @@
+//! Audio output backends.
+//!
+//! Playback is decoupled from the platform-specific output API behind
+//! the [`AudioSink`] trait so the player can run headless on Linux
+//! (ALSA, PulseAudio, JACK), macOS (CoreAudio) or Windows (WASAPI)
+//! without forking the playback loop, mirroring how this ecosystem
+//! usually puts platform backends and resolver paths behind Cargo
+//! features rather than compiling every platform's code everywhere.
+//! Volume is applied as a gain stage upstream of [`AudioSink::write`] so
+//! `set_volume` works the same regardless of which sink is compiled in.
+
+#[cfg(feature = "alsa-backend")]
+mod alsa;
+#[cfg(feature = "coreaudio-backend")]
+mod coreaudio;
+#[cfg(feature = "jack-backend")]
+mod jack;
+#[cfg(feature = "pulseaudio-backend")]
+mod pulseaudio;
+#[cfg(feature = "wasapi-backend")]
+mod wasapi;
+
+use crate::{
+    error::SdpPlayerError, jitter::JitterBuffer, metrics::Metrics, player_state::SharedPlayerState,
+    stream::Stream,
+};
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+use tokio::{sync::broadcast, time};
+
+/// Which compiled-in backend is used for output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    Alsa,
+    PulseAudio,
+    Jack,
+    CoreAudio,
+    Wasapi,
+}
+
+impl Backend {
+    /// Every backend compiled into this binary, in order of preference;
+    /// [`default_backend`] picks the first.
+    pub fn available() -> Vec<Backend> {
+        #[allow(unused_mut)]
+        let mut backends = Vec::new();
+        #[cfg(feature = "alsa-backend")]
+        backends.push(Backend::Alsa);
+        #[cfg(feature = "pulseaudio-backend")]
+        backends.push(Backend::PulseAudio);
+        #[cfg(feature = "jack-backend")]
+        backends.push(Backend::Jack);
+        #[cfg(feature = "coreaudio-backend")]
+        backends.push(Backend::CoreAudio);
+        #[cfg(feature = "wasapi-backend")]
+        backends.push(Backend::Wasapi);
+        backends
+    }
+
+    fn open(self, settings: &crate::preset::CustomStreamSettings) -> Result<Box<dyn AudioSink>, SdpPlayerError> {
+        match self {
+            #[cfg(feature = "alsa-backend")]
+            Backend::Alsa => alsa::open(settings),
+            #[cfg(feature = "pulseaudio-backend")]
+            Backend::PulseAudio => pulseaudio::open(settings),
+            #[cfg(feature = "jack-backend")]
+            Backend::Jack => jack::open(settings),
+            #[cfg(feature = "coreaudio-backend")]
+            Backend::CoreAudio => coreaudio::open(settings),
+            #[cfg(feature = "wasapi-backend")]
+            Backend::Wasapi => wasapi::open(settings),
+            #[allow(unreachable_patterns)]
+            _ => Err(SdpPlayerError::UnsupportedBackend(self)),
+        }
+    }
+}
+
+/// The sensible default backend for whatever platform this binary was
+/// compiled for; the first entry of [`Backend::available`] wins when
+/// more than one backend is compiled in.
+pub fn default_backend() -> Option<Backend> {
+    Backend::available().into_iter().next()
+}
+
+/// A platform audio output. Implementations receive PCM frames already
+/// at the stream's sample rate/channel count/bit depth and are
+/// responsible for clocking playback to the device; they are not
+/// expected to apply gain themselves.
+pub trait AudioSink: Send {
+    fn write(&mut self, frame: &[u8]) -> Result<(), SdpPlayerError>;
+}
+
+/// Applies a backend-agnostic gain stage to 16-bit little-endian PCM
+/// samples, so volume control doesn't depend on whichever sink is
+/// compiled in exposing one.
+fn apply_gain(frame: &[u8], volume: f32) -> Vec<u8> {
+    frame
+        .chunks_exact(2)
+        .flat_map(|pair| {
+            let sample = i16::from_le_bytes([pair[0], pair[1]]) as f32;
+            ((sample * volume).clamp(i16::MIN as f32, i16::MAX as f32) as i16).to_le_bytes()
+        })
+        .collect()
+}
+
+/// Computes a peak and RMS level, both normalized to `0.0..=1.0`, over a
+/// frame of 16-bit little-endian PCM samples, for the `Level` events
+/// published to `/events`.
+fn level_meter(frame: &[u8]) -> (f32, f32) {
+    let samples: Vec<f32> = frame
+        .chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]) as f32 / i16::MAX as f32)
+        .collect();
+    if samples.is_empty() {
+        return (0.0, 0.0);
+    }
+    let peak = samples.iter().fold(0.0_f32, |max, s| max.max(s.abs()));
+    let mean_square = samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32;
+    (peak, mean_square.sqrt())
+}
+
+/// Receives frames from `stream`, smooths them through the jitter
+/// buffer and writes the result to the selected platform backend,
+/// applying the current volume from `state` as a gain stage, until
+/// `stop` fires. `metrics` is the single instance also served at
+/// `/metrics`, so every counter bumped here is the one an operator
+/// actually sees.
+pub async fn play(
+    stream: Stream,
+    stop: broadcast::Sender<()>,
+    state: Arc<SharedPlayerState>,
+    metrics: Arc<Metrics>,
+) {
+    let mut stop_rx = stop.subscribe();
+
+    let settings = stream.settings();
+    let requested = settings.backend.or(state.snapshot().await.requested_backend);
+    let backend = match requested.filter(|b| Backend::available().contains(b)) {
+        Some(backend) => backend,
+        None => {
+            if let Some(requested) = requested {
+                log::warn!("{requested:?} backend requested but not compiled in, falling back to the default");
+            }
+            match default_backend() {
+                Some(backend) => backend,
+                None => {
+                    log::error!("no audio backend compiled into this binary");
+                    return;
+                }
+            }
+        }
+    };
+    let mut sink = match backend.open(settings) {
+        Ok(sink) => sink,
+        Err(e) => {
+            log::error!("failed to open {backend:?} audio backend: {e}");
+            return;
+        }
+    };
+    state.update(|s| s.backend = Some(backend)).await;
+    metrics.playing.set(1.0);
+    metrics.set_active_stream(
+        &settings.multicast_address.to_string(),
+        settings.sample_rate,
+        settings.channels,
+    );
+
+    let mut jitter = JitterBuffer::new(stream.jitter_config(), metrics.clone());
+
+    // Playout is paced by this ticker, not by packet arrival, so a
+    // silent gap in the multicast feed still gets concealment/silence
+    // at the right cadence instead of the sink stalling.
+    let packet_duration = Duration::from_secs_f64(settings.packet_time as f64 / 1000.0);
+    let mut playout = time::interval(packet_duration);
+    playout.set_missed_tick_behavior(time::MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            _ = stop_rx.recv() => {
+                log::info!("stopping playback");
+                break;
+            }
+            packet = stream.recv() => {
+                let Some((sequence_number, rtp_timestamp, payload)) = packet else {
+                    log::info!("stream ended");
+                    break;
+                };
+                jitter.insert(sequence_number, rtp_timestamp, payload);
+                state
+                    .update(|s| {
+                        s.stats.packets_received = metrics.packets_received.get() as u64;
+                        s.stats.packets_lost = metrics.packets_lost.get() as u64;
+                        s.stats.jitter_seconds = metrics.jitter_seconds.get();
+                    })
+                    .await;
+            }
+            _ = playout.tick() => {
+                let Some(frame) = jitter.next_playout_payload() else {
+                    continue;
+                };
+                let volume = state.snapshot().await.volume;
+                metrics.volume.set(volume as f64);
+                let frame = apply_gain(&frame, volume);
+                let (peak, rms) = level_meter(&frame);
+                state.publish_level(peak, rms);
+                if let Err(e) = sink.write(&frame) {
+                    log::error!("audio backend write failed: {e}");
+                    break;
+                }
+            }
+        }
+    }
+
+    metrics.playing.set(0.0);
+    state.update(|s| s.playing = false).await;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn halves_amplitude_at_half_volume() {
+        let frame = 1000i16.to_le_bytes().to_vec();
+        let gained = apply_gain(&frame, 0.5);
+        assert_eq!(i16::from_le_bytes([gained[0], gained[1]]), 500);
+    }
+
+    #[test]
+    fn clamps_instead_of_overflowing_on_gain_above_one() {
+        let frame = i16::MAX.to_le_bytes().to_vec();
+        let gained = apply_gain(&frame, 2.0);
+        assert_eq!(i16::from_le_bytes([gained[0], gained[1]]), i16::MAX);
+    }
+}
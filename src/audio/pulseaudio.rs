@@ -0,0 +1,41 @@
+//! PulseAudio output backend (`pulseaudio-backend` feature).
+
+use super::AudioSink;
+use crate::{error::SdpPlayerError, preset::CustomStreamSettings};
+use ::psimple::Simple;
+use ::pulse::{
+    sample::{Format, Spec},
+    stream::Direction,
+};
+
+pub(super) fn open(settings: &CustomStreamSettings) -> Result<Box<dyn AudioSink>, SdpPlayerError> {
+    let spec = Spec {
+        format: Format::S16le,
+        channels: settings.channels as u8,
+        rate: settings.sample_rate,
+    };
+    let simple = Simple::new(
+        None,
+        env!("CARGO_PKG_NAME"),
+        Direction::Playback,
+        None,
+        "SDP stream",
+        &spec,
+        None,
+        None,
+    )
+    .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))?;
+    Ok(Box::new(PulseAudioSink { simple }))
+}
+
+struct PulseAudioSink {
+    simple: Simple,
+}
+
+impl AudioSink for PulseAudioSink {
+    fn write(&mut self, frame: &[u8]) -> Result<(), SdpPlayerError> {
+        self.simple
+            .write(frame)
+            .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))
+    }
+}
@@ -0,0 +1,46 @@
+//! WASAPI output backend (`wasapi-backend` feature), for Windows.
+
+use super::AudioSink;
+use crate::{error::SdpPlayerError, preset::CustomStreamSettings};
+use ::wasapi::{get_default_device, Direction, SampleType, ShareMode, WaveFormat};
+
+pub(super) fn open(settings: &CustomStreamSettings) -> Result<Box<dyn AudioSink>, SdpPlayerError> {
+    let device = get_default_device(&Direction::Render)
+        .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))?;
+    let mut audio_client = device
+        .get_iaudioclient()
+        .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))?;
+
+    let format = WaveFormat::new(16, 16, &SampleType::Int, settings.sample_rate as usize, settings.channels as usize);
+    audio_client
+        .initialize_client(&format, 0, &Direction::Render, &ShareMode::Shared, false)
+        .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))?;
+    let render_client = audio_client
+        .get_audiorenderclient()
+        .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))?;
+    audio_client
+        .start_stream()
+        .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))?;
+
+    Ok(Box::new(WasapiSink {
+        _audio_client: audio_client,
+        render_client,
+    }))
+}
+
+struct WasapiSink {
+    _audio_client: ::wasapi::AudioClient,
+    render_client: ::wasapi::AudioRenderClient,
+}
+
+// SAFETY: the client handles are only ever touched from the single
+// playback task that owns this sink.
+unsafe impl Send for WasapiSink {}
+
+impl AudioSink for WasapiSink {
+    fn write(&mut self, frame: &[u8]) -> Result<(), SdpPlayerError> {
+        self.render_client
+            .write_to_device(frame.len(), frame, None)
+            .map_err(|e| SdpPlayerError::AudioBackend(e.to_string()))
+    }
+}
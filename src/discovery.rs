@@ -0,0 +1,374 @@
+//! SAP (Session Announcement Protocol, RFC 2974) multicast discovery.
+//!
+//! AES67/RTP senders periodically multicast the SDP of the streams they
+//! are sending on well-known SAP groups instead of requiring a listener
+//! to be handed the SDP out of band. This module joins those groups,
+//! parses the SAP envelope wrapped around each embedded SDP and keeps a
+//! [`Registry`] of the currently announced streams so they can be
+//! surfaced to clients (e.g. via the `/discover` endpoint) or turned into
+//! a [`Preset`](crate::preset::Preset) without the user having to paste
+//! in an SDP by hand.
+
+use crate::sdp::SessionDescriptor;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+use tokio::{net::UdpSocket, sync::RwLock};
+
+/// IPv4 global-scope SAP announcement group (RFC 2974).
+pub const SAP_IPV4_GLOBAL: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 255);
+/// IPv4 admin-scoped SAP announcement group, used by e.g. AES67 devices
+/// that don't want their announcements to leave the local network.
+pub const SAP_IPV4_ADMIN_SCOPE: Ipv4Addr = Ipv4Addr::new(239, 195, 255, 255);
+/// Well-known SAP port (RFC 2974).
+pub const SAP_PORT: u16 = 9875;
+
+/// How long a stream may go un-re-announced before it's considered gone.
+/// Announcers typically re-announce at least once every 30s; 5x that
+/// gives enough slack for the odd dropped packet without flapping.
+const STALE_AFTER: Duration = Duration::from_secs(150);
+
+/// Uniquely identifies an announced stream: SAP dedupes by the
+/// combination of the announcer's source address and its message-id
+/// hash, since a single source can announce several distinct sessions.
+pub type StreamKey = (IpAddr, u16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MessageType {
+    Announcement,
+    Deletion,
+}
+
+#[derive(Debug)]
+struct SapHeader {
+    message_type: MessageType,
+    message_id_hash: u16,
+    origin: IpAddr,
+}
+
+#[derive(Error, Debug)]
+enum SapError {
+    #[error("packet too short to contain a SAP header")]
+    Truncated,
+    #[error("unsupported SAP version {0}, expected 1")]
+    UnsupportedVersion(u8),
+    #[error("SAP packet is encrypted, which is not supported")]
+    Encrypted,
+    #[error("SAP packet is compressed, which is not supported")]
+    Compressed,
+}
+
+/// Parses the fixed SAP header from the front of `packet` and returns it
+/// together with the remainder of the packet (the optional payload-type
+/// string followed by the raw SDP).
+fn parse_sap_header(packet: &[u8]) -> Result<(SapHeader, &[u8]), SapError> {
+    if packet.len() < 4 {
+        return Err(SapError::Truncated);
+    }
+
+    let flags = packet[0];
+    let version = (flags >> 5) & 0b111;
+    if version != 1 {
+        return Err(SapError::UnsupportedVersion(version));
+    }
+    let ipv6 = flags & 0b0001_0000 != 0;
+    let message_type = if flags & 0b0000_0100 != 0 {
+        MessageType::Deletion
+    } else {
+        MessageType::Announcement
+    };
+    if flags & 0b0000_0010 != 0 {
+        return Err(SapError::Encrypted);
+    }
+    if flags & 0b0000_0001 != 0 {
+        return Err(SapError::Compressed);
+    }
+
+    let auth_len = packet[1] as usize;
+    let message_id_hash = u16::from_be_bytes([packet[2], packet[3]]);
+
+    let addr_len = if ipv6 { 16 } else { 4 };
+    let mut offset = 4;
+    if packet.len() < offset + addr_len {
+        return Err(SapError::Truncated);
+    }
+    let origin = if ipv6 {
+        let mut octets = [0u8; 16];
+        octets.copy_from_slice(&packet[offset..offset + 16]);
+        IpAddr::V6(Ipv6Addr::from(octets))
+    } else {
+        let mut octets = [0u8; 4];
+        octets.copy_from_slice(&packet[offset..offset + 4]);
+        IpAddr::V4(Ipv4Addr::from(octets))
+    };
+    offset += addr_len;
+
+    // RFC 2974 §3: auth_len counts 32-bit words of authentication data,
+    // not bytes.
+    let auth_len_bytes = auth_len * 4;
+    if packet.len() < offset + auth_len_bytes {
+        return Err(SapError::Truncated);
+    }
+    offset += auth_len_bytes;
+
+    Ok((
+        SapHeader {
+            message_type,
+            message_id_hash,
+            origin,
+        },
+        &packet[offset..],
+    ))
+}
+
+/// Strips the optional null-terminated MIME type in front of the SDP
+/// payload. We only ever expect `application/sdp`; anything else is left
+/// untouched and handled (or rejected) by the SDP parser instead.
+fn strip_payload_type(body: &[u8]) -> &[u8] {
+    match body.iter().position(|&b| b == 0) {
+        Some(nul) if body[..nul].starts_with(b"application/") => &body[nul + 1..],
+        _ => body,
+    }
+}
+
+/// A single stream currently being announced over SAP.
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoveredStream {
+    pub origin: IpAddr,
+    pub message_id_hash: u16,
+    pub sdp: SessionDescriptor,
+    pub raw_sdp: String,
+    #[serde(skip)]
+    last_seen: Instant,
+}
+
+/// Live registry of currently announced streams, fed by [`run`].
+#[derive(Debug, Default)]
+pub struct Registry {
+    streams: RwLock<HashMap<StreamKey, DiscoveredStream>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    async fn handle_packet(&self, packet: &[u8]) {
+        let (header, body) = match parse_sap_header(packet) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                log::debug!("dropping malformed SAP packet: {e}");
+                return;
+            }
+        };
+        let key = (header.origin, header.message_id_hash);
+
+        if header.message_type == MessageType::Deletion {
+            if self.streams.write().await.remove(&key).is_some() {
+                log::info!("stream from {} deleted via SAP", header.origin);
+            }
+            return;
+        }
+
+        let sdp_bytes = strip_payload_type(body);
+        let raw_sdp = match std::str::from_utf8(sdp_bytes) {
+            Ok(s) => s.to_owned(),
+            Err(e) => {
+                log::debug!("SAP payload from {} is not UTF-8: {e}", header.origin);
+                return;
+            }
+        };
+        let sdp = match SessionDescriptor::parse(&raw_sdp) {
+            Ok(sdp) => sdp,
+            Err(e) => {
+                log::debug!("failed to parse SDP announced by {}: {e}", header.origin);
+                return;
+            }
+        };
+
+        log::info!("discovered stream '{}' from {}", sdp.name(), header.origin);
+        self.streams.write().await.insert(
+            key,
+            DiscoveredStream {
+                origin: header.origin,
+                message_id_hash: header.message_id_hash,
+                sdp,
+                raw_sdp,
+                last_seen: Instant::now(),
+            },
+        );
+    }
+
+    fn evict_stale(streams: &mut HashMap<StreamKey, DiscoveredStream>) {
+        streams.retain(|_, stream| stream.last_seen.elapsed() < STALE_AFTER);
+    }
+
+    /// Current snapshot of announced streams, after dropping anything
+    /// that hasn't re-announced within the staleness window.
+    pub async fn snapshot(&self) -> Vec<DiscoveredStream> {
+        let mut streams = self.streams.write().await;
+        Self::evict_stale(&mut streams);
+        streams.values().cloned().collect()
+    }
+
+    pub async fn get(&self, key: &StreamKey) -> Option<DiscoveredStream> {
+        let mut streams = self.streams.write().await;
+        Self::evict_stale(&mut streams);
+        streams.get(key).cloned()
+    }
+}
+
+/// Joins the well-known SAP multicast groups and feeds every received
+/// packet into `registry` until the process exits or the socket errors.
+pub async fn run(registry: Arc<Registry>) -> std::io::Result<()> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, SAP_PORT)).await?;
+    for group in [SAP_IPV4_GLOBAL, SAP_IPV4_ADMIN_SCOPE] {
+        socket.join_multicast_v4(group, Ipv4Addr::UNSPECIFIED)?;
+    }
+    log::info!("listening for SAP announcements on port {SAP_PORT}");
+
+    let mut buf = [0u8; 65_535];
+    loop {
+        let (len, from) = socket.recv_from(&mut buf).await?;
+        log::debug!("received {len} byte SAP packet from {from}");
+        registry.handle_packet(&buf[..len]).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn announcement_packet() -> Vec<u8> {
+        let mut packet = vec![
+            0b0010_0000, // version 1, IPv4, announcement, unencrypted, uncompressed
+            0,           // no auth data
+            0x12,
+            0x34, // message id hash
+            10,
+            0,
+            0,
+            1, // origin 10.0.0.1
+        ];
+        packet.extend_from_slice(b"application/sdp\0");
+        packet.extend_from_slice(b"v=0\r\n");
+        packet
+    }
+
+    #[test]
+    fn parses_announcement_header() {
+        let packet = announcement_packet();
+        let (header, rest) = parse_sap_header(&packet).unwrap();
+        assert_eq!(header.message_type, MessageType::Announcement);
+        assert_eq!(header.message_id_hash, 0x1234);
+        assert_eq!(header.origin, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(strip_payload_type(rest), b"v=0\r\n");
+    }
+
+    #[test]
+    fn parses_deletion_flag() {
+        let mut packet = announcement_packet();
+        packet[0] |= 0b0000_0100;
+        let (header, _) = parse_sap_header(&packet).unwrap();
+        assert_eq!(header.message_type, MessageType::Deletion);
+    }
+
+    #[test]
+    fn rejects_unsupported_version() {
+        let mut packet = announcement_packet();
+        packet[0] = 0b0100_0000;
+        assert!(matches!(
+            parse_sap_header(&packet),
+            Err(SapError::UnsupportedVersion(2))
+        ));
+    }
+
+    #[test]
+    fn rejects_truncated_packet() {
+        assert!(matches!(parse_sap_header(&[0, 0]), Err(SapError::Truncated)));
+    }
+
+    #[test]
+    fn leaves_unrecognized_payload_type_untouched() {
+        let body = b"text/plain\0hello";
+        assert_eq!(strip_payload_type(body), b"text/plain\0hello");
+    }
+
+    #[test]
+    fn skips_auth_data_in_32_bit_words() {
+        let mut packet = vec![
+            0b0010_0000, // version 1, IPv4, announcement, unencrypted, uncompressed
+            2,           // 2 x 32-bit words = 8 bytes of auth data
+            0x12,
+            0x34,
+            10,
+            0,
+            0,
+            1, // origin 10.0.0.1
+        ];
+        packet.extend_from_slice(&[0xAA; 8]); // auth data, must be skipped whole
+        packet.extend_from_slice(b"application/sdp\0");
+        packet.extend_from_slice(b"v=0\r\n");
+
+        let (_, rest) = parse_sap_header(&packet).unwrap();
+        assert_eq!(strip_payload_type(rest), b"v=0\r\n");
+    }
+
+    fn announcement(origin: [u8; 4], message_id_hash: u16, sdp_name: &str) -> Vec<u8> {
+        let mut packet = vec![
+            0b0010_0000,
+            0,
+            (message_id_hash >> 8) as u8,
+            (message_id_hash & 0xff) as u8,
+            origin[0],
+            origin[1],
+            origin[2],
+            origin[3],
+        ];
+        packet.extend_from_slice(b"application/sdp\0");
+        packet.extend_from_slice(format!("v=0\r\no={sdp_name} 0 0 IN IP4 0.0.0.0\r\ns={sdp_name}\r\n").as_bytes());
+        packet
+    }
+
+    #[tokio::test]
+    async fn registry_dedupes_evicts_and_handles_deletion() {
+        let registry = Registry::new();
+        let key_a = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), 0x1234);
+        let key_b = (IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)), 0x5678);
+
+        registry.handle_packet(&announcement([10, 0, 0, 1], 0x1234, "stream-a")).await;
+        registry.handle_packet(&announcement([10, 0, 0, 2], 0x5678, "stream-b")).await;
+        assert_eq!(registry.snapshot().await.len(), 2);
+
+        // Re-announcing the same (origin, message id hash) updates the
+        // existing entry instead of creating a second one.
+        registry.handle_packet(&announcement([10, 0, 0, 1], 0x1234, "stream-a-renamed")).await;
+        let snapshot = registry.snapshot().await;
+        assert_eq!(snapshot.len(), 2);
+        assert!(snapshot.iter().any(|s| s.sdp.name() == "stream-a-renamed"));
+
+        // A deletion packet for stream A removes only that entry.
+        let mut deletion = announcement([10, 0, 0, 1], 0x1234, "stream-a-renamed");
+        deletion[0] |= 0b0000_0100;
+        registry.handle_packet(&deletion).await;
+        assert!(registry.get(&key_a).await.is_none());
+        assert!(registry.get(&key_b).await.is_some());
+
+        // An entry whose last_seen is older than STALE_AFTER is evicted
+        // on the next snapshot/get.
+        registry
+            .streams
+            .write()
+            .await
+            .get_mut(&key_b)
+            .unwrap()
+            .last_seen = Instant::now() - STALE_AFTER - Duration::from_secs(1);
+        assert!(registry.get(&key_b).await.is_none());
+        assert_eq!(registry.snapshot().await.len(), 0);
+    }
+}
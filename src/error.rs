@@ -0,0 +1,23 @@
+//! Crate-wide error type.
+//!
+//! Most fallible operations in `sdplay_lib` — parsing an SDP, opening a
+//! socket, opening an audio backend — funnel into a single error enum so
+//! callers (chiefly the HTTP handlers) have one type to convert into a
+//! response instead of matching on half a dozen.
+
+use crate::audio::Backend;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum SdpPlayerError {
+    #[error("failed to parse SDP: {0}")]
+    SdpParse(String),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to send stop signal: {0}")]
+    StopSignal(#[from] tokio::sync::broadcast::error::SendError<()>),
+    #[error("audio backend error: {0}")]
+    AudioBackend(String),
+    #[error("no {0:?} backend compiled into this binary")]
+    UnsupportedBackend(Backend),
+}
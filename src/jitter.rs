@@ -0,0 +1,240 @@
+//! Adaptive jitter buffer for the RTP playout path.
+//!
+//! Live multicast audio arrives reordered and with variable network
+//! delay. Feeding packets straight from the receive loop into the audio
+//! sink as they arrive causes audible glitches whenever two packets swap
+//! order or one is briefly delayed. This sits between the RTP receive
+//! loop and the audio sink: it reorders packets by sequence number,
+//! paces delivery from a playout clock derived from the stream's sample
+//! rate and packet time instead of from arrival time, and conceals the
+//! occasional late or lost packet rather than glitching.
+
+use crate::metrics::Metrics;
+use std::{collections::BTreeMap, sync::Arc, time::Duration, time::Instant};
+
+/// Converts raw 16-bit RTP sequence numbers into a monotonically
+/// increasing value, so that ordering and buffer keys are well-defined
+/// across a wraparound from 65535 back to 0.
+#[derive(Debug, Default)]
+struct SequenceExtender {
+    wraps: i64,
+    last_raw: Option<u16>,
+}
+
+impl SequenceExtender {
+    fn extend(&mut self, seq: u16) -> i64 {
+        if let Some(last_raw) = self.last_raw {
+            if last_raw > 0xC000 && seq < 0x4000 {
+                self.wraps += 1;
+            } else if last_raw < 0x4000 && seq > 0xC000 {
+                // A late, pre-wrap packet arriving after we already saw
+                // one from the other side of the rollover.
+                self.wraps -= 1;
+            }
+        }
+        self.last_raw = Some(seq);
+        self.wraps * 0x1_0000 + seq as i64
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct JitterBufferConfig {
+    pub sample_rate: u32,
+    /// RTP packet duration, in milliseconds (matches `CustomStreamSettings::packet_time`).
+    pub packet_time: f32,
+    pub target_latency: Duration,
+}
+
+/// How many consecutive estimated-jitter widths to buffer before the
+/// target latency clamp kicks in.
+const JITTER_MULTIPLE: f64 = 4.0;
+
+/// Reorders incoming RTP packets by sequence number and paces delivery
+/// to the audio sink against a playout clock, concealing late or lost
+/// packets instead of glitching.
+pub struct JitterBuffer {
+    config: JitterBufferConfig,
+    metrics: Arc<Metrics>,
+    extender: SequenceExtender,
+    slots: BTreeMap<i64, Vec<u8>>,
+    next_playout: Option<i64>,
+    highest_inserted: Option<i64>,
+    started_at: Instant,
+    /// RFC 3550 interarrival jitter estimate, in RTP timestamp units.
+    jitter: f64,
+    last_transit: Option<f64>,
+    last_emitted: Option<Vec<u8>>,
+}
+
+impl JitterBuffer {
+    pub fn new(config: JitterBufferConfig, metrics: Arc<Metrics>) -> Self {
+        Self {
+            config,
+            metrics,
+            extender: SequenceExtender::default(),
+            slots: BTreeMap::new(),
+            next_playout: None,
+            highest_inserted: None,
+            started_at: Instant::now(),
+            jitter: 0.0,
+            last_transit: None,
+            last_emitted: None,
+        }
+    }
+
+    /// Records arrival of a packet: updates the running jitter estimate
+    /// and buffers its payload for later playout, discarding it
+    /// immediately if it arrived after its playout deadline already
+    /// passed.
+    pub fn insert(&mut self, sequence_number: u16, rtp_timestamp: u32, payload: Vec<u8>) {
+        self.metrics.packets_received.inc();
+        let extended_seq = self.extender.extend(sequence_number);
+
+        let arrival = self.started_at.elapsed().as_secs_f64() * self.config.sample_rate as f64;
+        let transit = arrival - rtp_timestamp as f64;
+        if let Some(last_transit) = self.last_transit {
+            self.jitter += ((transit - last_transit).abs() - self.jitter) / 16.0;
+        }
+        self.last_transit = Some(transit);
+        self.metrics.jitter_seconds.set(self.jitter_seconds());
+
+        if self.highest_inserted.is_some_and(|highest| extended_seq < highest) {
+            self.metrics.packets_reordered.inc();
+        } else {
+            self.highest_inserted = Some(extended_seq);
+        }
+
+        if self.next_playout.is_some_and(|next| extended_seq < next) {
+            log::debug!("dropping late RTP packet #{sequence_number}");
+            return;
+        }
+
+        self.slots.insert(extended_seq, payload);
+        if self.next_playout.is_none() {
+            self.next_playout = Some(extended_seq);
+        }
+    }
+
+    /// Running jitter estimate, in seconds.
+    pub fn jitter_seconds(&self) -> f64 {
+        self.jitter / self.config.sample_rate as f64
+    }
+
+    fn packet_duration(&self) -> f64 {
+        self.config.packet_time as f64 / 1000.0
+    }
+
+    /// How many packets the buffer should hold before playout starts,
+    /// sized to a multiple of the current jitter estimate and clamped to
+    /// the configured target latency.
+    fn depth_packets(&self) -> usize {
+        let packet_duration = self.packet_duration();
+        let latency = (self.jitter_seconds() * JITTER_MULTIPLE)
+            .min(self.config.target_latency.as_secs_f64())
+            .max(packet_duration);
+        ((latency / packet_duration).ceil() as usize).max(1)
+    }
+
+    /// True once enough packets have been buffered to start pulling
+    /// audio from the buffer, and from then on once playout has emitted
+    /// a payload.
+    fn primed(&self) -> bool {
+        self.last_emitted.is_some() || self.slots.len() >= self.depth_packets()
+    }
+
+    /// Conceals a missing packet by replaying the previous one at
+    /// reduced gain, or silence if there is nothing to replay yet.
+    fn conceal(&self) -> Vec<u8> {
+        match &self.last_emitted {
+            Some(previous) => attenuate(previous),
+            None => Vec::new(),
+        }
+    }
+
+    /// Pulls the payload due at the current playout instant, advancing
+    /// the playout clock by one packet. Returns `None` while the buffer
+    /// is still priming.
+    pub fn next_playout_payload(&mut self) -> Option<Vec<u8>> {
+        if !self.primed() {
+            self.metrics.buffer_underruns.inc();
+            return None;
+        }
+
+        let next = self.next_playout?;
+        let payload = match self.slots.remove(&next) {
+            Some(payload) => payload,
+            None => {
+                self.metrics.packets_lost.inc();
+                self.conceal()
+            }
+        };
+        self.next_playout = Some(next + 1);
+        if !payload.is_empty() {
+            self.last_emitted = Some(payload.clone());
+        }
+        Some(payload)
+    }
+}
+
+/// Halves the amplitude of 16-bit little-endian PCM samples, used to
+/// make concealed packets audibly distinct from real audio instead of
+/// looping it at full volume.
+fn attenuate(payload: &[u8]) -> Vec<u8> {
+    payload
+        .chunks_exact(2)
+        .flat_map(|pair| {
+            let sample = i16::from_le_bytes([pair[0], pair[1]]);
+            (sample / 2).to_le_bytes()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config() -> JitterBufferConfig {
+        JitterBufferConfig {
+            sample_rate: 48_000,
+            packet_time: 1.0,
+            target_latency: Duration::from_millis(20),
+        }
+    }
+
+    #[test]
+    fn extends_sequence_across_wraparound() {
+        let mut extender = SequenceExtender::default();
+        assert_eq!(extender.extend(65_534), 65_534);
+        assert_eq!(extender.extend(65_535), 65_535);
+        assert_eq!(extender.extend(0), 65_536);
+        assert_eq!(extender.extend(1), 65_537);
+    }
+
+    #[test]
+    fn reorders_out_of_order_packets() {
+        let mut buffer = JitterBuffer::new(config(), Arc::new(Metrics::new()));
+        buffer.insert(1, 48, vec![1, 0]);
+        buffer.insert(0, 0, vec![0, 0]);
+        assert_eq!(buffer.next_playout_payload(), Some(vec![0, 0]));
+        assert_eq!(buffer.next_playout_payload(), Some(vec![1, 0]));
+    }
+
+    #[test]
+    fn conceals_missing_packet_with_attenuated_repeat() {
+        let mut buffer = JitterBuffer::new(config(), Arc::new(Metrics::new()));
+        buffer.insert(0, 0, vec![0x10, 0x00]);
+        buffer.insert(2, 96, vec![0x30, 0x00]);
+        assert_eq!(buffer.next_playout_payload(), Some(vec![0x10, 0x00]));
+        assert_eq!(buffer.next_playout_payload(), Some(vec![0x08, 0x00]));
+        assert_eq!(buffer.next_playout_payload(), Some(vec![0x30, 0x00]));
+    }
+
+    #[test]
+    fn drops_packets_arriving_after_their_playout_deadline() {
+        let mut buffer = JitterBuffer::new(config(), Arc::new(Metrics::new()));
+        buffer.insert(0, 0, vec![0, 0]);
+        assert_eq!(buffer.next_playout_payload(), Some(vec![0, 0]));
+        buffer.insert(0, 0, vec![9, 9]);
+        assert_eq!(buffer.slots.len(), 0);
+    }
+}
@@ -0,0 +1,117 @@
+//! Prometheus metrics for stream playback health.
+//!
+//! Counters and gauges live behind a single [`Metrics`] handle so the
+//! stream-receive loop, the jitter buffer and the audio-playback loop
+//! can all update the same series without reaching into a global. The
+//! `/metrics` endpoint renders them in the text exposition format via
+//! [`Metrics::render`].
+
+use prometheus::{Encoder, Gauge, GaugeVec, IntCounter, Opts, Registry, TextEncoder};
+
+/// Playback instrumentation for a single running player.
+pub struct Metrics {
+    registry: Registry,
+    pub packets_received: IntCounter,
+    pub packets_lost: IntCounter,
+    pub packets_reordered: IntCounter,
+    pub buffer_underruns: IntCounter,
+    pub jitter_seconds: Gauge,
+    pub playing: Gauge,
+    pub volume: Gauge,
+    pub active_stream_info: GaugeVec,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        let packets_received = IntCounter::new(
+            "sdplay_packets_received_total",
+            "Total number of RTP packets received.",
+        )
+        .expect("metric options are valid");
+        let packets_lost = IntCounter::new(
+            "sdplay_packets_lost_total",
+            "Total number of RTP packets that never arrived before their playout deadline.",
+        )
+        .expect("metric options are valid");
+        let packets_reordered = IntCounter::new(
+            "sdplay_packets_reordered_total",
+            "Total number of RTP packets received out of sequence order.",
+        )
+        .expect("metric options are valid");
+        let buffer_underruns = IntCounter::new(
+            "sdplay_buffer_underruns_total",
+            "Total number of times the jitter buffer had nothing to play out.",
+        )
+        .expect("metric options are valid");
+        let jitter_seconds = Gauge::new(
+            "sdplay_jitter_seconds",
+            "Current RFC 3550 interarrival jitter estimate, in seconds.",
+        )
+        .expect("metric options are valid");
+        let playing = Gauge::new("sdplay_playing", "1 if a stream is currently playing, else 0.")
+            .expect("metric options are valid");
+        let volume = Gauge::new("sdplay_volume", "Current output volume, from 0.0 to 1.0.")
+            .expect("metric options are valid");
+        let active_stream_info = GaugeVec::new(
+            Opts::new(
+                "sdplay_active_stream_info",
+                "Static info about the currently playing stream; always 1 while set.",
+            ),
+            &["multicast_addr", "sample_rate", "channels"],
+        )
+        .expect("metric options are valid");
+
+        for collector in [
+            Box::new(packets_received.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(packets_lost.clone()),
+            Box::new(packets_reordered.clone()),
+            Box::new(buffer_underruns.clone()),
+            Box::new(jitter_seconds.clone()),
+            Box::new(playing.clone()),
+            Box::new(volume.clone()),
+            Box::new(active_stream_info.clone()),
+        ] {
+            registry
+                .register(collector)
+                .expect("metric names are unique");
+        }
+
+        Self {
+            registry,
+            packets_received,
+            packets_lost,
+            packets_reordered,
+            buffer_underruns,
+            jitter_seconds,
+            playing,
+            volume,
+            active_stream_info,
+        }
+    }
+
+    /// Renders all registered series in the Prometheus text exposition
+    /// format, ready to be served as the body of `/metrics`.
+    pub fn render(&self) -> Result<String, prometheus::Error> {
+        let families = self.registry.gather();
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&families, &mut buffer)?;
+        Ok(String::from_utf8(buffer).expect("prometheus output is always valid UTF-8"))
+    }
+
+    /// Records which stream is currently active, replacing any
+    /// previously-set labels.
+    pub fn set_active_stream(&self, multicast_addr: &str, sample_rate: u32, channels: u16) {
+        self.active_stream_info.reset();
+        self.active_stream_info
+            .with_label_values(&[multicast_addr, &sample_rate.to_string(), &channels.to_string()])
+            .set(1.0);
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,86 @@
+//! Shared playback state, broadcast to subscribers over WebSocket.
+//!
+//! Previously each handler owned (or faked) its own little piece of
+//! state. `PlayerState` is the single source of truth for what's
+//! currently playing, the volume and live stream statistics; handlers
+//! read and write it through [`SharedPlayerState`], and every change is
+//! broadcast as a [`PlayerEvent`] so clients can subscribe instead of
+//! polling `/status`.
+
+use crate::{audio::Backend, SessionDescriptor};
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::{broadcast, RwLock};
+
+/// Live statistics for the currently playing stream.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StreamStats {
+    pub packets_received: u64,
+    pub packets_lost: u64,
+    pub jitter_seconds: f64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PlayerState {
+    pub current_sdp: Option<SessionDescriptor>,
+    pub playing: bool,
+    pub volume: f32,
+    pub stats: StreamStats,
+    /// Backend actually in use for the current (or most recent) stream.
+    pub backend: Option<Backend>,
+    /// Backend requested via `POST /backend`, consulted by `audio::play`
+    /// in preference to [`crate::audio::default_backend`] the next time
+    /// playback starts.
+    pub requested_backend: Option<Backend>,
+}
+
+/// Broadcast to `/events` subscribers whenever [`PlayerState`] changes,
+/// plus periodic audio level meters that aren't part of the persistent
+/// state.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum PlayerEvent {
+    StateChanged(PlayerState),
+    Level { peak: f32, rms: f32 },
+}
+
+/// Owns the current [`PlayerState`] plus the broadcast channel that
+/// notifies subscribers when it changes.
+pub struct SharedPlayerState {
+    state: RwLock<PlayerState>,
+    events: broadcast::Sender<PlayerEvent>,
+}
+
+impl SharedPlayerState {
+    pub fn new() -> Arc<Self> {
+        let (events, _) = broadcast::channel(32);
+        Arc::new(Self {
+            state: RwLock::new(PlayerState {
+                volume: 0.5,
+                ..Default::default()
+            }),
+            events,
+        })
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<PlayerEvent> {
+        self.events.subscribe()
+    }
+
+    pub async fn snapshot(&self) -> PlayerState {
+        self.state.read().await.clone()
+    }
+
+    /// Applies `update` to the state and broadcasts the result to every
+    /// subscriber.
+    pub async fn update(&self, update: impl FnOnce(&mut PlayerState)) {
+        let mut state = self.state.write().await;
+        update(&mut state);
+        let _ = self.events.send(PlayerEvent::StateChanged(state.clone()));
+    }
+
+    /// Broadcasts a level-meter reading without touching persistent state.
+    pub fn publish_level(&self, peak: f32, rms: f32) {
+        let _ = self.events.send(PlayerEvent::Level { peak, rms });
+    }
+}
@@ -1,10 +1,10 @@
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, io, net::SocketAddrV4, path::PathBuf};
+use std::{collections::HashMap, io, net::SocketAddrV4, path::PathBuf, time::Duration};
 use thiserror::Error;
 use tokio::fs;
 use url::Url;
 
-use crate::sdp::BitDepth;
+use crate::{audio::Backend, sdp::BitDepth};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "camelCase")]
@@ -27,6 +27,20 @@ pub struct CustomStreamSettings {
     pub channels: u16,
     pub sample_rate: u32,
     pub packet_time: f32,
+    /// Target playout latency for the jitter buffer. Defaults to 20ms,
+    /// a reasonable compromise between resilience to network jitter and
+    /// end-to-end delay for live AES67 sources.
+    #[serde(default = "default_target_latency")]
+    pub target_latency: Duration,
+    /// Audio backend to play this stream through. `None` means the one
+    /// requested via `POST /backend`, or else
+    /// [`crate::audio::default_backend`].
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub backend: Option<Backend>,
+}
+
+fn default_target_latency() -> Duration {
+    Duration::from_millis(20)
 }
 
 pub async fn load_presets() -> PresetResult<HashMap<String, Preset>> {
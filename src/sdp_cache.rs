@@ -0,0 +1,214 @@
+//! HTTP fetching and on-disk caching for URL-based SDP presets.
+//!
+//! `Preset::sdp_url` lets a preset point at an SDP document served over
+//! HTTP(S) instead of embedding it. This fetches that document, parses
+//! it the same way a raw SDP body would be, and caches the last-known-
+//! good copy under the app config dir (the same directory the preset
+//! loader uses) so playback can fall back to it if the announcing host
+//! is temporarily unreachable. Revalidation uses a conditional GET with
+//! whichever of `ETag`/`Last-Modified` the server previously returned.
+
+use crate::{error::SdpPlayerError, SessionDescriptor};
+use reqwest::{header, Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    path::PathBuf,
+};
+use thiserror::Error;
+use tokio::fs;
+use url::Url;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    raw_sdp: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum SdpFetchError {
+    #[error("no config dir found")]
+    NoConfigDir,
+    #[error("io error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("request error: {0}")]
+    RequestError(#[from] reqwest::Error),
+    #[error("json error: {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("server returned {0} and no cached copy is available")]
+    Unreachable(StatusCode),
+    #[error("failed to parse cached/fetched SDP: {0}")]
+    Sdp(SdpPlayerError),
+}
+
+pub type SdpFetchResult<T> = Result<T, SdpFetchError>;
+
+fn cache_file_for(url: &Url) -> SdpFetchResult<PathBuf> {
+    let base_dirs = directories::BaseDirs::new().ok_or(SdpFetchError::NoConfigDir)?;
+    let app_config_dir = base_dirs
+        .config_dir()
+        .join(env!("CARGO_PKG_NAME"))
+        .join("sdp-cache");
+
+    let mut hasher = DefaultHasher::new();
+    url.as_str().hash(&mut hasher);
+    Ok(app_config_dir.join(format!("{:016x}.json", hasher.finish())))
+}
+
+async fn read_cache_entry(path: &PathBuf) -> Option<CacheEntry> {
+    let data = fs::read(path).await.ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+async fn write_cache_entry(path: &PathBuf, entry: &CacheEntry) -> SdpFetchResult<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    fs::write(path, serde_json::to_vec(entry)?).await?;
+    Ok(())
+}
+
+fn parse(raw_sdp: &str) -> SdpFetchResult<SessionDescriptor> {
+    SessionDescriptor::parse(raw_sdp).map_err(SdpFetchError::Sdp)
+}
+
+/// What to do once a fetch attempt has resolved, given whether a cached
+/// copy exists. `status` is `None` when the request itself failed
+/// (connection error, timeout, ...) rather than coming back with a
+/// status code. Pulled out of [`fetch_sdp`] as a pure function so the
+/// cache-fallback behavior can be unit-tested without a network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CacheDecision {
+    /// Use the cached copy: either the server said 304, the request
+    /// failed, or it returned a non-2xx status, and a cache exists.
+    UseCache,
+    /// No cache to fall back to, so the failure is terminal.
+    Fail,
+    /// Got a fresh 2xx response; read and cache the new body.
+    Fetch,
+}
+
+fn decide(status: Option<StatusCode>, has_cache: bool) -> CacheDecision {
+    let falls_back_to_cache = match status {
+        None => true,
+        Some(status) => status == StatusCode::NOT_MODIFIED || !status.is_success(),
+    };
+    match (falls_back_to_cache, has_cache) {
+        (true, true) => CacheDecision::UseCache,
+        (true, false) => CacheDecision::Fail,
+        (false, _) => CacheDecision::Fetch,
+    }
+}
+
+/// Fetches the SDP document at `url`, revalidating against any
+/// previously cached copy and falling back to it if the host can't be
+/// reached or returns an error.
+pub async fn fetch_sdp(url: &Url) -> SdpFetchResult<SessionDescriptor> {
+    let cache_path = cache_file_for(url)?;
+    let cached = read_cache_entry(&cache_path).await;
+
+    let client = Client::new();
+    let mut request = client.get(url.clone());
+    if let Some(entry) = &cached {
+        if let Some(etag) = &entry.etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &entry.last_modified {
+            request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let send_result = request.send().await;
+    let status = send_result.as_ref().ok().map(|response| response.status());
+
+    match decide(status, cached.is_some()) {
+        CacheDecision::Fail => match send_result {
+            Ok(response) => Err(SdpFetchError::Unreachable(response.status())),
+            Err(e) => Err(e.into()),
+        },
+        CacheDecision::UseCache => {
+            let entry = cached.expect("has_cache was true");
+            match &send_result {
+                Ok(response) if response.status() == StatusCode::NOT_MODIFIED => {
+                    log::debug!("{url} not modified, using cached copy");
+                }
+                Ok(response) => {
+                    log::warn!(
+                        "{url} returned {}, falling back to cached copy",
+                        response.status()
+                    );
+                }
+                Err(e) => {
+                    log::warn!("failed to fetch {url}, falling back to cached copy: {e}");
+                }
+            }
+            parse(&entry.raw_sdp)
+        }
+        CacheDecision::Fetch => {
+            let response = send_result.expect("Fetch decision implies a successful response");
+            let etag = response
+                .headers()
+                .get(header::ETAG)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from);
+            let last_modified = response
+                .headers()
+                .get(header::LAST_MODIFIED)
+                .and_then(|v| v.to_str().ok())
+                .map(String::from);
+            let raw_sdp = response.text().await?;
+            let sd = parse(&raw_sdp)?;
+
+            write_cache_entry(
+                &cache_path,
+                &CacheEntry {
+                    raw_sdp: raw_sdp.clone(),
+                    etag,
+                    last_modified,
+                },
+            )
+            .await?;
+
+            Ok(sd)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fetches_fresh_body_on_success() {
+        assert_eq!(decide(Some(StatusCode::OK), false), CacheDecision::Fetch);
+        assert_eq!(decide(Some(StatusCode::OK), true), CacheDecision::Fetch);
+    }
+
+    #[test]
+    fn falls_back_to_cache_on_not_modified() {
+        assert_eq!(
+            decide(Some(StatusCode::NOT_MODIFIED), true),
+            CacheDecision::UseCache
+        );
+    }
+
+    #[test]
+    fn falls_back_to_cache_on_non_2xx_status() {
+        assert_eq!(
+            decide(Some(StatusCode::INTERNAL_SERVER_ERROR), true),
+            CacheDecision::UseCache
+        );
+        assert_eq!(
+            decide(Some(StatusCode::INTERNAL_SERVER_ERROR), false),
+            CacheDecision::Fail
+        );
+    }
+
+    #[test]
+    fn falls_back_to_cache_on_request_error() {
+        assert_eq!(decide(None, true), CacheDecision::UseCache);
+        assert_eq!(decide(None, false), CacheDecision::Fail);
+    }
+}